@@ -1,22 +1,49 @@
 mod server;
 
-use server::http::{HttpResponseBuilder, HttpServer, HttpStatusCode};
+use std::time::Duration;
+
+use server::http::{HttpMethod, HttpResponseBuilder, HttpServer, HttpStatusCode};
+use server::router::Router;
 
 fn main() {
     let addr = "127.0.0.1:3000";
 
-    let server = HttpServer::new(|request| {
-        let response = HttpResponseBuilder::new();
+    let router = Router::new()
+        .route("/", HttpMethod::Get, |request| {
+            println!("{} {}", request.method.as_str(), request.path);
+
+            HttpResponseBuilder::new()
+                .status(HttpStatusCode::Ok)
+                .header("Content-Type", "text/html")
+                .header("X-Powered-By", "rust/basic-http-server")
+                .body("<h1>Hello, world!</h1>")
+                .build()
+        })
+        .route("/hello/{name}", HttpMethod::Get, |request| {
+            println!("{} {}", request.method.as_str(), request.path);
+
+            let greeting = format!("Hello, {}!", request.params.get("name").map_or("", |v| v));
+
+            HttpResponseBuilder::new()
+                .status(HttpStatusCode::Ok)
+                .header("Content-Type", "text/plain")
+                .body_bytes(greeting.as_bytes())
+                .build()
+        })
+        .not_found(|request| {
+            println!("{} {}", request.method.as_str(), request.path);
 
-        println!("{:?}", request);
+            HttpResponseBuilder::new()
+                .status(HttpStatusCode::NotFound)
+                .body("Not Found")
+                .build()
+        });
 
-        response
-            .status(HttpStatusCode::Ok)
-            .header("Content-Type", "text/html")
-            .header("X-Powered-By", "rust/basic-http-server")
-            .body("<h1>Hello, world!</h1>")
-            .build()
-    });
+    let server = HttpServer::with_router(router)
+        .keep_alive(Duration::from_secs(30))
+        .max_body_size(1024 * 1024)
+        .expect_continue(true)
+        .workers(4);
 
     println!("Server is starting on address: {}", addr);
 
@@ -2,6 +2,14 @@ use std::collections::HashMap;
 use std::io::{self, Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::pool::ThreadPool;
+
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(5);
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub enum HttpStatusCode {
@@ -46,7 +54,7 @@ impl HttpStatusCode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum HttpMethod {
     Get,
     Post,
@@ -58,6 +66,21 @@ pub enum HttpMethod {
     Trace,
 }
 
+impl HttpMethod {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Options => "OPTIONS",
+            HttpMethod::Trace => "TRACE",
+        };
+    }
+}
+
 impl FromStr for HttpMethod {
     type Err = ();
 
@@ -82,37 +105,157 @@ pub struct HttpRequest {
     pub path: String,
     pub headers: HashMap<String, String>,
     pub body: String,
+    pub params: HashMap<String, String>,
 }
 
 pub struct HttpResponse {
     pub status: HttpStatusCode,
     pub headers: HashMap<String, String>,
-    pub body: String,
+    pub body: Vec<u8>,
+}
+
+/// Status codes for which the response must not carry a body, per RFC 7230 §3.3.3
+/// (1xx informational, 204 No Content, 304 Not Modified).
+fn forbids_body(status_code: i32) -> bool {
+    return (100..200).contains(&status_code) || status_code == 204 || status_code == 304;
+}
+
+/// Formats `time` as an RFC 1123 date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, the format
+/// HTTP/1.1 servers are expected to send in the `Date` header.
+fn format_http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let is_leap_year = |year: u64| (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+
+    let total_seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+    let mut days = total_seconds / 86_400;
+    let seconds_of_day = total_seconds % 86_400;
+
+    let weekday = WEEKDAYS[(days % 7) as usize];
+    let hour = seconds_of_day / 3_600;
+    let minute = (seconds_of_day % 3_600) / 60;
+    let second = seconds_of_day % 60;
+
+    let mut year = 1970u64;
+
+    loop {
+        let days_in_year = if is_leap_year(year) { 366 } else { 365 };
+
+        if days < days_in_year {
+            break;
+        }
+
+        days -= days_in_year;
+        year += 1;
+    }
+
+    let month_lengths = [
+        31,
+        if is_leap_year(year) { 29 } else { 28 },
+        31, 30, 31, 30, 31, 31, 30, 31, 30, 31,
+    ];
+
+    let mut month = 0;
+
+    for (index, length) in month_lengths.into_iter().enumerate() {
+        if days < length {
+            month = index;
+            break;
+        }
+
+        days -= length;
+    }
+
+    return format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        weekday,
+        days + 1,
+        MONTHS[month],
+        year,
+        hour,
+        minute,
+        second
+    );
 }
 
-impl ToString for HttpResponse {
-    fn to_string(&self) -> String {
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub enum JsonError {
+    UnsupportedMediaType,
+    Decode(serde_json::Error),
+    Encode(serde_json::Error),
+}
+
+#[cfg(feature = "json")]
+impl HttpRequest {
+    /// Deserializes `self.body` as JSON, requiring a `Content-Type: application/json`
+    /// request header.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, JsonError> {
+        let is_json = self
+            .headers
+            .get("Content-Type")
+            .map(|value| {
+                value
+                    .split(';')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .eq_ignore_ascii_case("application/json")
+            })
+            .unwrap_or(false);
+
+        if !is_json {
+            return Err(JsonError::UnsupportedMediaType);
+        }
+
+        return serde_json::from_str(&self.body).map_err(JsonError::Decode);
+    }
+}
+
+impl HttpResponse {
+    /// Serializes the response to the bytes written on the wire, filling in
+    /// `Content-Length` and `Date` headers the user didn't already set (skipping
+    /// `Content-Length` for status codes that forbid a body).
+    /// Operates on bytes rather than `String` so binary bodies survive intact.
+    pub fn to_bytes(&self) -> Vec<u8> {
         let (status_code, status) = self.status.clone().to_http_status();
 
-        let mut headers = String::new();
+        let mut headers = self.headers.clone();
+
+        if !forbids_body(status_code) && !headers.contains_key("Content-Length") {
+            headers.insert("Content-Length".to_string(), self.body.len().to_string());
+        }
+
+        headers
+            .entry("Date".to_string())
+            .or_insert_with(|| format_http_date(SystemTime::now()));
 
-        for (key, value) in &self.headers {
-            headers.push_str(&format!("{}: {}\r\n", key, value));
+        let mut head = format!("HTTP/1.1 {} {}\r\n", status_code, status);
+
+        for (key, value) in &headers {
+            head.push_str(&format!("{}: {}\r\n", key, value));
         }
 
-        let response = format!(
-            "HTTP/1.1 {} {}\r\n{}\r\n{}",
-            status_code, status, headers, self.body
-        );
+        head.push_str("\r\n");
+
+        let mut bytes = head.into_bytes();
 
-        return response;
+        if !forbids_body(status_code) {
+            bytes.extend_from_slice(&self.body);
+        }
+
+        return bytes;
     }
 }
 
 pub struct HttpResponseBuilder {
     status: HttpStatusCode,
     headers: HashMap<String, String>,
-    body: String,
+    body: Vec<u8>,
 }
 
 impl HttpResponseBuilder {
@@ -120,7 +263,7 @@ impl HttpResponseBuilder {
         return HttpResponseBuilder {
             status: HttpStatusCode::Ok,
             headers: HashMap::new(),
-            body: "".to_string(),
+            body: Vec::new(),
         };
     }
 
@@ -135,7 +278,13 @@ impl HttpResponseBuilder {
     }
 
     pub fn body(mut self, body: &str) -> HttpResponseBuilder {
-        self.body = body.to_string();
+        self.body = body.as_bytes().to_vec();
+        return self;
+    }
+
+    /// Sets a raw byte body, e.g. binary payloads that aren't valid UTF-8.
+    pub fn body_bytes(mut self, body: &[u8]) -> HttpResponseBuilder {
+        self.body = body.to_vec();
         return self;
     }
 
@@ -148,114 +297,417 @@ impl HttpResponseBuilder {
     }
 }
 
+#[cfg(feature = "json")]
+impl HttpResponseBuilder {
+    /// Serializes `value` as the response body and sets `Content-Type: application/json`
+    /// (an accurate `Content-Length` is filled in automatically on serialization).
+    pub fn json<T: serde::Serialize>(self, value: &T) -> Result<HttpResponseBuilder, JsonError> {
+        let body = serde_json::to_string(value).map_err(JsonError::Encode)?;
+
+        return Ok(self.header("Content-Type", "application/json").body(&body));
+    }
+}
+
+type Handler = dyn Fn(HttpRequest) -> HttpResponse + Send + Sync;
+
 pub struct HttpServer {
-    handler: Box<dyn Fn(HttpRequest) -> HttpResponse>,
+    handler: Arc<Handler>,
+    keep_alive: Duration,
+    max_body_size: usize,
+    expect_continue: bool,
+    workers: usize,
 }
 
 impl HttpServer {
-    pub fn new(handler: impl Fn(HttpRequest) -> HttpResponse + 'static) -> HttpServer {
+    pub fn new(handler: impl Fn(HttpRequest) -> HttpResponse + Send + Sync + 'static) -> HttpServer {
         return HttpServer {
-            handler: Box::new(handler),
+            handler: Arc::new(handler),
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            expect_continue: true,
+            workers: default_workers(),
         };
     }
 
+    pub fn with_router(router: super::router::Router) -> HttpServer {
+        return HttpServer {
+            handler: Arc::new(move |request| router.dispatch(request)),
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            expect_continue: true,
+            workers: default_workers(),
+        };
+    }
+
+    /// Sets the idle timeout for HTTP/1.1 persistent connections (default: 5 seconds).
+    /// The connection is closed once this much time passes without the client sending
+    /// a new request.
+    pub fn keep_alive(mut self, duration: Duration) -> HttpServer {
+        self.keep_alive = duration;
+        return self;
+    }
+
+    /// Caps the size of a request body, whether framed by `Content-Length` or decoded
+    /// from `Transfer-Encoding: chunked`, rejecting larger bodies with `400 Bad Request`.
+    pub fn max_body_size(mut self, size: usize) -> HttpServer {
+        self.max_body_size = size;
+        return self;
+    }
+
+    /// Controls whether an `Expect: 100-continue` header is answered automatically
+    /// with an interim `100 Continue` before the body is read (default: enabled). This
+    /// only suppresses the interim response; the body is still read in full before the
+    /// handler runs either way, so it does not let a handler reject a request ahead of
+    /// the body arriving.
+    pub fn expect_continue(mut self, enabled: bool) -> HttpServer {
+        self.expect_continue = enabled;
+        return self;
+    }
+
+    /// Sets the number of worker threads that accepted connections are dispatched to
+    /// (default: the number of available CPUs).
+    pub fn workers(mut self, count: usize) -> HttpServer {
+        self.workers = count.max(1);
+        return self;
+    }
+
     pub fn listen(&self, addr: &str) -> io::Result<()> {
         let listener = TcpListener::bind(addr)?;
+        let pool = ThreadPool::new(self.workers);
+
+        let config = ConnectionConfig {
+            keep_alive: self.keep_alive,
+            max_body_size: self.max_body_size,
+            expect_continue: self.expect_continue,
+        };
 
         while let Ok((mut stream, _)) = listener.accept() {
-            let Ok(request) = parse_request(&mut stream) else {
-                continue;
-            };
+            let handler = Arc::clone(&self.handler);
 
-            let response = (self.handler)(request);
+            pool.execute(move || serve_connection(&mut stream, &handler, config));
+        }
+
+        return Ok(());
+    }
+}
+
+fn default_workers() -> usize {
+    return std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1);
+}
+
+#[derive(Clone, Copy)]
+struct ConnectionConfig {
+    keep_alive: Duration,
+    max_body_size: usize,
+    expect_continue: bool,
+}
+
+fn serve_connection(stream: &mut TcpStream, handler: &Arc<Handler>, config: ConnectionConfig) {
+    let mut leftover = Vec::new();
 
-            if let Ok(_) = stream.write(response.to_string().as_bytes()) {
-                match stream.flush() {
-                    Err(err) => println!("Error: {}", err),
-                    _ => (),
-                };
+    loop {
+        if let Err(err) = stream.set_read_timeout(Some(config.keep_alive)) {
+            println!("Error: {}", err);
+            return;
+        }
+
+        let request = match parse_request(stream, config.max_body_size, config.expect_continue, leftover)
+        {
+            Ok((request, next_leftover)) => {
+                leftover = next_leftover;
+                request
+            }
+            Err(ParseError::MalformedStartLine)
+            | Err(ParseError::HeadersTooLarge)
+            | Err(ParseError::MalformedChunkSize)
+            | Err(ParseError::MalformedContentLength)
+            | Err(ParseError::BodyTooLarge) => {
+                let response = HttpResponseBuilder::new()
+                    .status(HttpStatusCode::BadRequest)
+                    .build();
+
+                let _ = stream.write(&response.to_bytes());
+                let _ = stream.flush();
+
+                return;
             }
+            Err(ParseError::UnexpectedEof) | Err(ParseError::Io(_)) => return,
+        };
+
+        let keep_alive = should_keep_alive(&request);
+
+        let mut response = handler(request);
+
+        response
+            .headers
+            .entry("Connection".to_string())
+            .or_insert_with(|| {
+                if keep_alive {
+                    "keep-alive".to_string()
+                } else {
+                    "close".to_string()
+                }
+            });
+
+        if let Ok(_) = stream.write(&response.to_bytes()) {
+            match stream.flush() {
+                Err(err) => println!("Error: {}", err),
+                _ => (),
+            };
         }
 
-        return Ok(());
+        if !keep_alive {
+            return;
+        }
     }
 }
 
-enum ParseError {
-    Unknown,
+fn should_keep_alive(request: &HttpRequest) -> bool {
+    return match request.headers.get("Connection") {
+        Some(value) => !value.eq_ignore_ascii_case("close"),
+        None => true,
+    };
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    MalformedStartLine,
+    HeadersTooLarge,
+    MalformedChunkSize,
+    MalformedContentLength,
+    BodyTooLarge,
+    UnexpectedEof,
+    Io(io::Error),
+}
+
+fn find_header_terminator(buffer: &[u8]) -> Option<usize> {
+    return buffer
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|pos| pos + 4);
 }
 
-fn parse_request(stream: &mut TcpStream) -> Result<HttpRequest, ParseError> {
-    let mut buffer = [0; 2048];
+fn find_crlf(buffer: &[u8], from: usize) -> Option<usize> {
+    return buffer[from..]
+        .windows(2)
+        .position(|window| window == b"\r\n")
+        .map(|pos| from + pos);
+}
+
+fn fill_until(stream: &mut TcpStream, pending: &mut Vec<u8>, min_len: usize) -> Result<(), ParseError> {
+    let mut chunk = [0; 2048];
 
-    stream.read(&mut buffer).unwrap();
+    while pending.len() < min_len {
+        let bytes_read = stream.read(&mut chunk).map_err(ParseError::Io)?;
 
-    let source = String::from_utf8_lossy(&buffer);
+        if bytes_read == 0 {
+            return Err(ParseError::UnexpectedEof);
+        }
+
+        pending.extend_from_slice(&chunk[..bytes_read]);
+    }
+
+    return Ok(());
+}
+
+/// Reads a `Content-Length`-framed body, returning it alongside any trailing bytes
+/// already read past the body (e.g. the start of a pipelined follow-up request) so
+/// callers can feed them back into the next parse instead of discarding them.
+fn read_fixed_body(
+    stream: &mut TcpStream,
+    initial: &[u8],
+    content_length: usize,
+) -> Result<(Vec<u8>, Vec<u8>), ParseError> {
+    let mut buffer = initial.to_vec();
+
+    fill_until(stream, &mut buffer, content_length)?;
+
+    let leftover = buffer.split_off(content_length);
+
+    return Ok((buffer, leftover));
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body: a sequence of hex-size-prefixed chunks
+/// terminated by a zero-size chunk and an optional trailer section, per RFC 7230 §4.1.
+/// Returns the body alongside any trailing bytes read past it (e.g. a pipelined
+/// follow-up request) so callers can feed them back into the next parse.
+fn read_chunked_body(
+    stream: &mut TcpStream,
+    initial: &[u8],
+    max_body_size: usize,
+) -> Result<(Vec<u8>, Vec<u8>), ParseError> {
+    let mut pending = initial.to_vec();
+    let mut cursor = 0;
+    let mut body = Vec::new();
+
+    loop {
+        while find_crlf(&pending, cursor).is_none() {
+            let min_len = pending.len() + 1;
+            fill_until(stream, &mut pending, min_len)?;
+        }
 
-    let mut lines = source.split("\r\n");
+        let size_line_end = find_crlf(&pending, cursor).unwrap();
+
+        let size_line = std::str::from_utf8(&pending[cursor..size_line_end])
+            .map_err(|_| ParseError::MalformedChunkSize)?;
+
+        let size_str = size_line.split(';').next().unwrap_or("").trim();
+
+        let chunk_size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| ParseError::MalformedChunkSize)?;
+
+        cursor = size_line_end + 2;
+
+        if chunk_size == 0 {
+            loop {
+                match find_crlf(&pending, cursor) {
+                    Some(pos) if pos == cursor => {
+                        cursor = pos + 2;
+                        break;
+                    }
+                    Some(pos) => cursor = pos + 2,
+                    None => {
+                        let min_len = pending.len() + 1;
+                        fill_until(stream, &mut pending, min_len)?;
+                    }
+                }
+            }
+
+            break;
+        }
+
+        if body.len() + chunk_size > max_body_size {
+            return Err(ParseError::BodyTooLarge);
+        }
+
+        fill_until(stream, &mut pending, cursor + chunk_size + 2)?;
+
+        body.extend_from_slice(&pending[cursor..cursor + chunk_size]);
+        cursor += chunk_size + 2;
+    }
+
+    let leftover = pending.split_off(cursor);
+
+    return Ok((body, leftover));
+}
+
+/// Parses the next request off `stream`. `leftover` carries bytes already read past
+/// the end of the previous request's body (e.g. the start of a pipelined follow-up
+/// request on a persistent connection) and is consumed before reading any more from
+/// the stream. Returns the request alongside whatever trailing bytes were read past
+/// its own body, for the caller to pass back in on the next call.
+fn parse_request(
+    stream: &mut TcpStream,
+    max_body_size: usize,
+    expect_continue: bool,
+    leftover: Vec<u8>,
+) -> Result<(HttpRequest, Vec<u8>), ParseError> {
+    let mut buffer = leftover;
+    let mut chunk = [0; 2048];
+
+    if !buffer.is_empty() {
+        stream.set_read_timeout(None).map_err(ParseError::Io)?;
+    }
+
+    let header_end = loop {
+        if let Some(pos) = find_header_terminator(&buffer) {
+            break pos;
+        }
+
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Err(ParseError::HeadersTooLarge);
+        }
+
+        let bytes_read = stream.read(&mut chunk).map_err(ParseError::Io)?;
+
+        if bytes_read == 0 {
+            return Err(ParseError::UnexpectedEof);
+        }
+
+        buffer.extend_from_slice(&chunk[..bytes_read]);
+
+        // The request has started arriving: stop applying the idle keep-alive timeout
+        // so a slow-but-live body upload isn't mistaken for an idle connection.
+        stream.set_read_timeout(None).map_err(ParseError::Io)?;
+    };
+
+    let header_source = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let mut lines = header_source.split("\r\n");
 
     let Some(first_line) = lines.next() else {
-        return Err(ParseError::Unknown);
+        return Err(ParseError::MalformedStartLine);
     };
 
     let mut parts = first_line.split_whitespace();
 
     let Some(Ok(method)) = parts.next().map(|method| method.parse::<HttpMethod>()) else {
-        return Err(ParseError::Unknown);
+        return Err(ParseError::MalformedStartLine);
     };
 
     let Some(path) = parts.next().map(|path| path.to_string()) else {
-        return Err(ParseError::Unknown);
+        return Err(ParseError::MalformedStartLine);
     };
 
     let mut headers = HashMap::new();
 
-    for line in lines.clone() {
+    for line in lines {
         if line.is_empty() {
-            break;
+            continue;
         }
 
         let mut parts = line.splitn(2, ": ");
 
-        let key = parts.next().unwrap().to_string();
-        let value = parts.next().unwrap().to_string();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
 
-        headers.insert(key, value);
+        headers.insert(key.to_string(), value.to_string());
     }
 
-    let content_length = headers
-        .get("Content-Length")
-        .map(|value| value.parse::<usize>())
-        .unwrap_or(Ok(0))
-        .unwrap();
-
-    let body = lines
-        .skip(headers.len() + 1)
-        .collect::<Vec<&str>>()
-        .join("\r\n");
-
-    let body = body.trim_matches(char::from(0));
-    let left_to_read = content_length - body.len();
-
-    if left_to_read == 0 {
-        return Ok(HttpRequest {
-            method,
-            path,
-            headers,
-            body: body.to_string(),
-        });
+    let wants_continue = expect_continue
+        && headers
+            .get("Expect")
+            .map(|value| value.eq_ignore_ascii_case("100-continue"))
+            .unwrap_or(false);
+
+    if wants_continue {
+        stream
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+            .map_err(ParseError::Io)?;
+        stream.flush().map_err(ParseError::Io)?;
     }
 
-    let mut buffer = vec![0; left_to_read];
+    let is_chunked = headers
+        .get("Transfer-Encoding")
+        .map(|value| value.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false);
+
+    let (body, leftover) = if is_chunked {
+        read_chunked_body(stream, &buffer[header_end..], max_body_size)?
+    } else {
+        let content_length = match headers.get("Content-Length") {
+            Some(value) => value
+                .parse::<usize>()
+                .map_err(|_| ParseError::MalformedContentLength)?,
+            None => 0,
+        };
 
-    stream.read(&mut buffer).unwrap();
+        if content_length > max_body_size {
+            return Err(ParseError::BodyTooLarge);
+        }
 
-    let body = format!("{}{}", body, String::from_utf8_lossy(&buffer));
+        read_fixed_body(stream, &buffer[header_end..], content_length)?
+    };
 
-    return Ok(HttpRequest {
+    let request = HttpRequest {
         method,
         path,
         headers,
-        body,
-    });
+        body: String::from_utf8_lossy(&body).into_owned(),
+        params: HashMap::new(),
+    };
+
+    return Ok((request, leftover));
 }
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use super::http::{HttpMethod, HttpRequest, HttpResponse, HttpResponseBuilder, HttpStatusCode};
+
+enum PathSegment {
+    Static(String),
+    Param(String),
+}
+
+fn compile_pattern(path: &str) -> Vec<PathSegment> {
+    return path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                return PathSegment::Param(segment[1..segment.len() - 1].to_string());
+            }
+
+            return PathSegment::Static(segment.to_string());
+        })
+        .collect();
+}
+
+fn match_pattern(pattern: &[PathSegment], path: &str) -> Option<HashMap<String, String>> {
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+    if segments.len() != pattern.len() {
+        return None;
+    }
+
+    let mut params = HashMap::new();
+
+    for (segment, matcher) in segments.iter().zip(pattern) {
+        match matcher {
+            PathSegment::Static(expected) => {
+                if expected != segment {
+                    return None;
+                }
+            }
+            PathSegment::Param(name) => {
+                params.insert(name.clone(), segment.to_string());
+            }
+        }
+    }
+
+    return Some(params);
+}
+
+struct Route {
+    method: HttpMethod,
+    pattern: Vec<PathSegment>,
+    handler: Box<dyn Fn(HttpRequest) -> HttpResponse + Send + Sync>,
+}
+
+/// Path and method based dispatcher for `HttpServer`.
+pub struct Router {
+    routes: Vec<Route>,
+    not_found: Box<dyn Fn(HttpRequest) -> HttpResponse + Send + Sync>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        return Router {
+            routes: Vec::new(),
+            not_found: Box::new(|_request| {
+                HttpResponseBuilder::new()
+                    .status(HttpStatusCode::NotFound)
+                    .body("Not Found")
+                    .build()
+            }),
+        };
+    }
+
+    pub fn route(
+        mut self,
+        path: &str,
+        method: HttpMethod,
+        handler: impl Fn(HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> Router {
+        self.routes.push(Route {
+            method,
+            pattern: compile_pattern(path),
+            handler: Box::new(handler),
+        });
+
+        return self;
+    }
+
+    pub fn not_found(
+        mut self,
+        handler: impl Fn(HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    ) -> Router {
+        self.not_found = Box::new(handler);
+        return self;
+    }
+
+    pub fn dispatch(&self, mut request: HttpRequest) -> HttpResponse {
+        let mut allowed_methods: Vec<&HttpMethod> = Vec::new();
+
+        for route in &self.routes {
+            let Some(params) = match_pattern(&route.pattern, &request.path) else {
+                continue;
+            };
+
+            if route.method != request.method {
+                allowed_methods.push(&route.method);
+                continue;
+            }
+
+            request.params = params;
+
+            return (route.handler)(request);
+        }
+
+        if !allowed_methods.is_empty() {
+            let allow = allowed_methods
+                .iter()
+                .map(|method| method.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+
+            return HttpResponseBuilder::new()
+                .status(HttpStatusCode::MethodNotAllowed)
+                .header("Allow", &allow)
+                .build();
+        }
+
+        return (self.not_found)(request);
+    }
+}